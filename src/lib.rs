@@ -8,6 +8,8 @@ Changes can be rolled back to the last commit with [`Tx::rollback`](./struct.Tx.
 
 A trait, [`Tx::AsTx`](./trait.AsTx.html) can be imported to implement `Self::tx(&mut self) -> Tx<Self>` on `&mut T`
 
+The `serde_json` feature adds `Tx::set_pointer` and `Tx::remove_pointer` for editing a `serde_json::Value` by JSON Pointer
+
 # Example
 ```
 use tx::Tx;
@@ -25,13 +27,13 @@ impl S {
 }
 
 let mut s = S{ d: vec![] };
-assert_eq!(s.d, vec![]);
+assert!(s.d.is_empty());
 {
     let mut s = s.tx();
     s.add(1);
     assert_eq!(s.d, vec![1]);
 }
-assert_eq!(s.d, vec![]);
+assert!(s.d.is_empty());
 {
     let mut s = s.tx();
     s.add(1);
@@ -53,36 +55,570 @@ assert_eq!(s.d, vec![1, 2]);
 ```
 */
 
+// the bounds keep `Tx` as `Send` and `Sync` as the value it borrows
+type Hook<'a, T> = Box<dyn FnMut(&T) + Send + Sync + 'a>;
+type CommitHook<'a, T> = Box<dyn FnMut(&T, &T) + Send + Sync + 'a>;
+type CloneFn<'a, T> = Box<dyn Fn(&T) -> T + Send + Sync + 'a>;
+
+const SUSPENDED: &str =
+    "the borrow is only given up by Tx::suspend, which consumes the transaction";
+
 /// A 'transaction' pointer
-pub struct Tx<'a, T>(&'a mut T, T, bool);
+pub struct Tx<'a, T> {
+    scratch: Option<&'a mut T>,
+    state: State<T>,
+    clone: CloneFn<'a, T>,
+    panic_slot: Option<&'a mut Option<T>>,
+    rollback_hook: Option<Hook<'a, T>>,
+    commit_hooks: Vec<CommitHook<'a, T>>,
+}
+
+// everything which doesn't borrow, so it can be kept by a `SuspendedTx`
+struct State<T> {
+    initial: Option<T>,
+    origin: Option<T>,
+    last_commit: Option<std::time::Instant>,
+    require_commit: bool,
+    // the mutation count at the last commit or rollback, for `require_commit`
+    settled_at: u64,
+    save: bool,
+    mutations: u64,
+    commits_since_rollback: u32,
+}
+
+impl<T> Default for State<T> {
+    fn default() -> Self {
+        Self {
+            initial: None,
+            origin: None,
+            last_commit: None,
+            require_commit: false,
+            settled_at: 0,
+            save: false,
+            mutations: 0,
+            commits_since_rollback: 0,
+        }
+    }
+}
 
 impl<'a, T: Clone> Tx<'a, T> {
     /// Creates a new `Tx` by mutably borrowing a type
     pub fn new(d: &'a mut T) -> Self {
-        let clone = d.clone();
-        Self(d, clone, false)
+        Self::with_clone_fn(d, T::clone)
+    }
+    /// Creates a new `Tx` for the value behind a smart pointer, such as a `Box`, `RefMut` or mutex guard
+    ///
+    /// The snapshot is taken of the pointed-to value, not of the pointer itself
+    pub fn from_deref<D>(d: &'a mut D) -> Self
+    where
+        D: std::ops::DerefMut<Target = T>,
+    {
+        Self::new(&mut **d)
+    }
+    /// Creates a new `Tx` from the parts returned by `Tx::into_parts`
+    ///
+    /// `initial` is the state to roll back to, and `committed` is whether the current value is kept on drop.
+    /// If it isn't, `d` may already differ from `initial`, so this doesn't start out as `Tx::is_noop`
+    pub fn from_parts(d: &'a mut T, initial: T, committed: bool) -> Self {
+        let mut tx = Self::with_parts(d, initial, Box::new(T::clone), committed);
+        tx.state.mutations = u64::from(!committed);
+        tx
+    }
+    /// Keeps `mirror` updated with the committed value
+    ///
+    /// `mirror` is only updated on a commit, so it never sees changes which are in progress or reverted.
+    /// It stops being updated once the transaction is suspended, see `Tx::suspend`
+    pub fn bind_mirror(&mut self, mirror: &'a mut T)
+    where
+        T: Send + Sync,
+    {
+        self.commit_hooks
+            .push(Box::new(move |_, new| mirror.clone_from(new)))
+    }
+    /// Starts an independent [`TxOwned`](./struct.TxOwned.html) from a snapshot of the current value
+    ///
+    /// Nothing done to the fork affects this transaction, or the value it borrows
+    pub fn fork(&self) -> TxOwned<T> {
+        TxOwned::new((**self).clone())
+    }
+    /// Runs `f` in a new transaction, committing it if `f` returns `Ok`
+    ///
+    /// The error is boxed so that different error types can be propagated with `?`
+    pub fn scope_result<R>(
+        d: &'a mut T,
+        f: impl FnOnce(&mut Tx<'a, T>) -> Result<R, Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Result<R, Box<dyn std::error::Error + Send + Sync>> {
+        let mut tx = Self::new(d);
+        let res = f(&mut tx)?;
+        tx.commit();
+        Ok(res)
+    }
+    /// Runs `f` in a new transaction until it returns `Ok`, at most `max_attempts` times
+    ///
+    /// The transaction is committed once `f` succeeds. After a failed attempt the value is restored
+    /// to how it was before the first one, even if `f` committed before failing,
+    /// so every attempt starts from the same state. `f` is always run at least once
+    pub fn retry<R, E>(
+        d: &mut T,
+        max_attempts: usize,
+        mut f: impl FnMut(&mut Tx<'_, T>) -> Result<R, E>,
+    ) -> Result<R, E> {
+        let start = d.clone();
+        let mut attempts = 1;
+        loop {
+            let mut tx = Tx::new(&mut *d);
+            match f(&mut tx) {
+                Ok(res) => {
+                    tx.commit();
+                    return Ok(res);
+                }
+                Err(err) => {
+                    drop(tx);
+                    d.clone_from(&start);
+                    if attempts >= max_attempts {
+                        return Err(err);
+                    }
+                    attempts += 1
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T> Tx<'a, T> {
+    /// Creates a read-only guard with the same shape as a `Tx`, without taking a snapshot
+    pub fn begin_readonly(d: &'a T) -> TxRead<'a, T> {
+        TxRead(d)
+    }
+    /// Creates a new `Tx` which uses `clone` instead of [`Clone`] to take snapshots
+    ///
+    /// `clone` is used both for taking the snapshot on commit and for restoring it on rollback.
+    /// This allows `T` to not implement [`Clone`], or to snapshot only part of its state.
+    /// `clone` can capture what it needs for that, such as a handle to share or a value to reset to
+    pub fn with_clone_fn(d: &'a mut T, clone: impl Fn(&T) -> T + Send + Sync + 'a) -> Self {
+        let initial = clone(d);
+        Self::with_parts(d, initial, Box::new(clone), false)
+    }
+    fn with_parts(d: &'a mut T, initial: T, clone: CloneFn<'a, T>, save: bool) -> Self {
+        Self {
+            scratch: Some(d),
+            state: State {
+                initial: Some(initial),
+                save,
+                ..State::default()
+            },
+            clone,
+            panic_slot: None,
+            rollback_hook: None,
+            commit_hooks: Vec::new(),
+        }
     }
     /// Commits the changes
     pub fn commit(&mut self) {
-        let Tx(scratch, initial, save) = self;
-        std::mem::replace(initial, scratch.clone());
-        *save = true;
+        let snapshot = (self.clone)(&**self);
+        let previous = self.state.initial.replace(snapshot);
+        self.committed(previous.as_ref());
+    }
+    fn committed(&mut self, previous: Option<&T>) {
+        self.run_commit_hooks(previous);
+        self.state.last_commit = Some(std::time::Instant::now());
+        self.state.commits_since_rollback += 1;
+        self.state.settled_at = self.state.mutations;
+        self.state.save = true;
+    }
+    fn run_commit_hooks(&mut self, previous: Option<&T>) {
+        let current = self.scratch.as_deref().expect(SUSPENDED);
+        for hook in &mut self.commit_hooks {
+            hook(previous.unwrap_or(current), current)
+        }
+    }
+    // after `Tx::detach_baseline` nothing can be rolled back, so the current value stands in for the baseline
+    fn baseline(&self) -> &T {
+        self.state.initial.as_ref().unwrap_or(&**self)
+    }
+    /// Commits the changes, returning what `differ` computed from the previous and the new state
+    pub fn commit_returning_diff<D>(&mut self, differ: impl FnOnce(&T, &T) -> D) -> D {
+        let diff = differ(self.baseline(), &**self);
+        self.commit();
+        diff
+    }
+    /// Runs `f` on the value and then commits the changes, including those made by `f`
+    pub fn commit_map(&mut self, f: impl FnOnce(&mut T)) {
+        f(self);
+        self.commit();
+    }
+    /// Commits the changes, unless the last commit happened less than `min_interval` ago
+    ///
+    /// Returns whether a commit happened
+    pub fn commit_debounced(&mut self, min_interval: std::time::Duration) -> bool {
+        if let Some(last) = self.state.last_commit {
+            if last.elapsed() < min_interval {
+                return false;
+            }
+        }
+        self.commit();
+        true
     }
     /// Roll back to previous commit (or the initial state)
     ///
     /// This also acts like creating a new "subtranscation"
+    ///
+    /// This does nothing after `Tx::detach_baseline`, until the next commit
     pub fn rollback(&mut self) {
-        let Tx(scratch, initial, save) = self;
-        std::mem::replace(*scratch, initial.clone());
-        *save = false
+        let initial = match &self.state.initial {
+            Some(initial) => initial,
+            None => return,
+        };
+        let current = self.scratch.as_deref_mut().expect(SUSPENDED);
+        if let Some(hook) = &mut self.rollback_hook {
+            hook(current)
+        }
+        *current = (self.clone)(initial);
+        self.state.mutations += 1;
+        self.state.settled_at = self.state.mutations;
+        self.state.commits_since_rollback = 0;
+        self.state.save = false
+    }
+    /// Commits the changes and frees the state to roll back to, for when it won't be needed anymore
+    ///
+    /// Unlike `Tx::commit`, this doesn't take a snapshot. Until the next commit, nothing can be rolled back:
+    /// `Tx::rollback` does nothing, and anything comparing against the last commit uses the current value
+    pub fn detach_baseline(&mut self) {
+        let previous = self.state.initial.take();
+        self.committed(previous.as_ref());
+    }
+    /// Returns how many commits happened since the last rollback (or since this was created)
+    pub fn commits_since_rollback(&self) -> u32 {
+        self.state.commits_since_rollback
+    }
+    /// Rolls back to the previous commit (or the initial state), returning a snapshot of it
+    ///
+    /// After `Tx::detach_baseline` nothing is rolled back, and this is a snapshot of the current value
+    pub fn restore_and_clone(&mut self) -> T {
+        self.rollback();
+        (self.clone)(self.baseline())
+    }
+    /// Commits the changes and ends the transaction, releasing the borrow
+    ///
+    /// If the value was never mutably accessed (see `Tx::is_noop`), it is kept without taking a snapshot
+    pub fn commit_and_release(mut self) {
+        if self.is_noop() {
+            self.state.save = true
+        } else {
+            self.commit()
+        }
+    }
+    /// Detaches the transaction from the borrow, returning both
+    ///
+    /// The current value is left in the borrow, and the transaction can be continued with
+    /// [`SuspendedTx::resume`](./struct.SuspendedTx.html#method.resume).
+    /// If the `SuspendedTx` is dropped instead, the current value is kept.
+    ///
+    /// The hooks, the function from `Tx::with_clone_fn` and the slot from `Tx::capture_on_panic`
+    /// borrow for as long as this transaction, so they are dropped here. The resumed transaction
+    /// starts without them, and uses [`Clone`] to take snapshots
+    pub fn suspend(mut self) -> (SuspendedTx<T>, &'a mut T) {
+        let d = self.scratch.take().expect(SUSPENDED);
+        let state = std::mem::take(&mut self.state);
+        (SuspendedTx { state }, d)
+    }
+    /// Decomposes the transaction into the borrow, the state to roll back to and whether it was committed
+    ///
+    /// Nothing is rolled back: the borrow keeps the current value, and restoring the state to roll back to
+    /// is now up to the caller. Any hooks or other configuration are discarded.
+    /// After `Tx::detach_baseline` the state to roll back to is a snapshot of the current value
+    pub fn into_parts(mut self) -> (&'a mut T, T, bool) {
+        let initial = match self.state.initial.take() {
+            Some(initial) => initial,
+            None => (self.clone)(&self),
+        };
+        let (suspended, d) = self.suspend();
+        (d, initial, suspended.state.save)
+    }
+    /// Stores the current value in `slot` if this is dropped without committing while panicking
+    ///
+    /// This keeps the changes that would be lost by unwinding available for diagnostics.
+    /// The slot is released by `Tx::suspend`, so a resumed transaction doesn't fill it
+    pub fn capture_on_panic(mut self, slot: &'a mut Option<T>) -> Self {
+        self.panic_slot = Some(slot);
+        self
+    }
+    /// Keeps a snapshot of the state to roll back to, for `Tx::at_origin`
+    ///
+    /// This costs an extra snapshot, which is kept for as long as the transaction
+    pub fn track_origin(mut self) -> Self {
+        self.state.origin = Some((self.clone)(self.baseline()));
+        self
+    }
+    /// Sets a hook which is called with the discarded value whenever changes are reverted
+    ///
+    /// This happens on `Tx::rollback` and when dropped without committing, but never on a commit.
+    /// The hook is dropped by `Tx::suspend`, so a resumed transaction reverts without calling it
+    pub fn with_rollback_hook(mut self, hook: impl FnMut(&T) + Send + Sync + 'a) -> Self {
+        self.rollback_hook = Some(Box::new(hook));
+        self
+    }
+    /// Calls `on_change` on every commit which changes the field returned by `get`
+    ///
+    /// `on_change` is given the field from the previous commit (or the initial state) and the new one.
+    /// Commits after the transaction is suspended are no longer watched, see `Tx::suspend`
+    pub fn watch_field<F, G, H>(&mut self, get: G, on_change: H)
+    where
+        F: PartialEq,
+        G: Fn(&T) -> &F + Send + Sync + 'a,
+        H: Fn(&F, &F) + Send + Sync + 'a,
+    {
+        self.commit_hooks.push(Box::new(move |old, new| {
+            let (old, new) = (get(old), get(new));
+            if old != new {
+                on_change(old, new)
+            }
+        }))
+    }
+    /// Replaces the current value with the result of a [`three_way`](./fn.three_way.html) merge
+    ///
+    /// The state at the last commit (or the initial state) is used as the common base,
+    /// and the current value as `ours`
+    pub fn merge_three_way(&mut self, theirs: &T, merge: impl FnOnce(&T, &T, &T) -> T) {
+        let merged = three_way(self.baseline(), &**self, theirs, merge);
+        **self = merged;
+    }
+    /// Makes dropping this with changes since the last commit or rollback panic in debug builds
+    ///
+    /// The changes are still reverted first. `Tx::settle` counts as committing even when it skips
+    /// an unchanged value. In release builds this does nothing
+    pub fn require_commit(mut self) -> Self {
+        self.state.require_commit = true;
+        self
+    }
+    /// Commits the changes if `commit` is true, otherwise rolls them back
+    pub fn commit_or_rollback(&mut self, commit: bool) {
+        if commit {
+            self.commit()
+        } else {
+            self.rollback()
+        }
+    }
+    /// Replaces the current value with `snapshot`, also making it the state to roll back to
+    ///
+    /// Since `snapshot` is kept even without a commit, this counts as one for the commit hooks
+    /// (such as `Tx::bind_mirror`) and for `Tx::require_commit`
+    pub fn restore_from(&mut self, snapshot: T) {
+        let restored = (self.clone)(&snapshot);
+        **self = restored;
+        let previous = self.state.initial.replace(snapshot);
+        self.run_commit_hooks(previous.as_ref());
+        self.state.settled_at = self.state.mutations;
+    }
+    /// Mutably borrows the state to roll back to
+    ///
+    /// This changes what `Tx::rollback` (and dropping without committing) restores,
+    /// without touching the current value. The origin kept by `Tx::track_origin` is unaffected.
+    /// After `Tx::detach_baseline` a snapshot of the current value is taken first
+    pub fn baseline_mut(&mut self) -> &mut T {
+        let (clone, current) = (&self.clone, self.scratch.as_deref().expect(SUSPENDED));
+        self.state.initial.get_or_insert_with(|| clone(current))
+    }
+    /// Swaps the current value with the current value of `other`
+    ///
+    /// The states to roll back to are not swapped, each transaction still rolls back to its own
+    pub fn swap_with(&mut self, other: &mut Tx<'_, T>) {
+        std::mem::swap(&mut **self, &mut **other);
+    }
+    /// Applies `patch` to the current value using `patcher`
+    ///
+    /// Like any other change, this is undone by a rollback
+    pub fn apply_patch<D>(&mut self, patch: &D, patcher: impl Fn(&mut T, &D)) {
+        patcher(self, patch)
+    }
+    /// Borrows a part of the current value
+    pub fn map_ref<U>(&self, f: impl FnOnce(&T) -> &U) -> &U {
+        f(self)
+    }
+    /// Mutably borrows a part of the current value
+    ///
+    /// Changes made through it are part of this transaction
+    pub fn map_mut<U>(&mut self, f: impl FnOnce(&mut T) -> &mut U) -> &mut U {
+        f(self)
+    }
+    /// Runs a fallible computation against the current value, without modifying it
+    pub fn probe<R, E>(&self, f: impl FnOnce(&T) -> Result<R, E>) -> Result<R, E> {
+        f(self)
+    }
+    /// Returns whether the value was never mutably accessed through this transaction
+    ///
+    /// Unlike comparing the value against its previous state, this is a cheap check.
+    /// A value that was modified and then changed back is not a no-op
+    pub fn is_noop(&self) -> bool {
+        self.state.mutations == 0
+    }
+    /// Captures a token for the current state of the value, to be checked with `Tx::changed_since`
+    pub fn token(&self) -> VersionToken {
+        VersionToken(self.state.mutations)
+    }
+    /// Returns whether the value was mutably accessed since `token` was captured
+    ///
+    /// Like `Tx::is_noop`, this doesn't compare values, so it is a cheap check.
+    /// The token must have been captured from this transaction
+    pub fn changed_since(&self, token: VersionToken) -> bool {
+        self.state.mutations != token.0
+    }
+}
+
+/// A token for the state of a value, returned by [`Tx::token`](./struct.Tx.html#method.token)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VersionToken(u64);
+
+impl<'a, T> Tx<'a, Vec<T>> {
+    /// Splits the collection at `at`, like [`Vec::split_off`]
+    ///
+    /// The returned tail belongs to the caller and isn't part of the transaction.
+    /// Rolling back only restores the full collection in the working value
+    pub fn split_off_tx(&mut self, at: usize) -> Vec<T> {
+        self.split_off(at)
+    }
+    /// Throws away any elements changed since the last commit (or the initial state)
+    ///
+    /// Unlike [`Vec::clear`] this doesn't empty the collection, it restores the committed elements.
+    /// This is the same as `Tx::rollback`
+    pub fn clear_uncommitted(&mut self) {
+        self.rollback()
+    }
+    /// Commits the changes, failing if the collection is longer than `max_len`
+    ///
+    /// Nothing is committed on an error, and the value is no longer kept on drop,
+    /// so it is restored to the last commit unless the changes are fixed and committed
+    pub fn commit_limited(&mut self, max_len: usize) -> Result<(), LimitExceeded> {
+        let len = self.len();
+        if len > max_len {
+            self.state.save = false;
+            return Err(LimitExceeded { max_len, len });
+        }
+        self.commit();
+        Ok(())
+    }
+}
+
+/// The error returned by [`Tx::commit_limited`](./struct.Tx.html#method.commit_limited)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LimitExceeded {
+    /// The longest the collection was allowed to be
+    pub max_len: usize,
+    /// The length of the collection when committing
+    pub len: usize,
+}
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "limit exceeded: {} elements, at most {} allowed",
+            self.len, self.max_len
+        )
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+impl<'a, T: PartialEq> Tx<'a, T> {
+    /// Returns whether the current value is equal to the initial state, regardless of any commits
+    ///
+    /// This returns `None` unless the initial state was kept with `Tx::track_origin`
+    pub fn at_origin(&self) -> Option<bool> {
+        let origin = self.state.origin.as_ref()?;
+        Some(**self == *origin)
+    }
+    /// Commits the changes, failing if the value is the same as at the last commit (or the initial state)
+    pub fn commit_nonempty(&mut self) -> Result<(), NoChangesError> {
+        if **self == *self.baseline() {
+            return Err(NoChangesError);
+        }
+        self.commit();
+        Ok(())
+    }
+    /// Commits the changes if the value differs from the last commit (or the initial state)
+    ///
+    /// Otherwise nothing is done, which avoids taking a snapshot of an unchanged value
+    pub fn settle(&mut self) {
+        if **self != *self.baseline() {
+            self.commit()
+        } else {
+            self.state.settled_at = self.state.mutations
+        }
+    }
+    /// Returns whether the current value is equal to `candidate`
+    pub fn equivalent_to(&self, candidate: &T) -> bool {
+        **self == *candidate
+    }
+}
+
+/// The error returned by [`Tx::commit_nonempty`](./struct.Tx.html#method.commit_nonempty)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NoChangesError;
+
+impl std::fmt::Display for NoChangesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("no changes to commit")
+    }
+}
+
+impl std::error::Error for NoChangesError {}
+
+impl<'a, T: PartialEq> Tx<'a, Vec<T>> {
+    /// Summarizes how the collection differs from the last commit (or the initial state)
+    ///
+    /// This is a positional comparison rather than a minimal edit script:
+    /// an element inserted at the front counts every following element as changed
+    pub fn diff_stats(&self) -> DiffStats {
+        let (current, initial) = (&**self, self.baseline());
+        let common = current.len().min(initial.len());
+        DiffStats {
+            added: current.len() - common,
+            removed: initial.len() - common,
+            changed: current
+                .iter()
+                .zip(initial.iter())
+                .filter(|(a, b)| a != b)
+                .count(),
+        }
     }
 }
 
+/// A summary of changes, returned by [`Tx::diff_stats`](./struct.Tx.html#method.diff_stats)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiffStats {
+    /// Elements past the end of the previous state
+    pub added: usize,
+    /// Elements missing from the end of the previous state
+    pub removed: usize,
+    /// Elements at the same position which are different
+    pub changed: usize,
+}
+
 impl<'a, T> Drop for Tx<'a, T> {
     fn drop(&mut self) {
-        let Tx(scratch, initial, save) = self;
-        if !*save {
-            std::mem::swap(initial, *scratch);
+        let current = match self.scratch.as_deref_mut() {
+            Some(current) => current,
+            None => return,
+        };
+        // a value that was never touched has nothing to revert, and swapping in the snapshot
+        // would still drop whatever a custom clone function left out of it
+        if !self.state.save && self.state.mutations != 0 {
+            if std::thread::panicking() {
+                if let Some(slot) = self.panic_slot.take() {
+                    *slot = Some((self.clone)(current));
+                }
+            }
+            if let Some(hook) = &mut self.rollback_hook {
+                hook(current)
+            }
+            if let Some(initial) = &mut self.state.initial {
+                std::mem::swap(initial, current);
+            }
+        }
+        let uncommitted = self.state.mutations != self.state.settled_at;
+        if self.state.require_commit && uncommitted && !std::thread::panicking() {
+            debug_assert!(false, "transaction was dropped without being committed");
         }
     }
 }
@@ -90,25 +626,26 @@ impl<'a, T> Drop for Tx<'a, T> {
 impl<'a, T> std::ops::Deref for Tx<'a, T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        self.scratch.as_deref().expect(SUSPENDED)
     }
 }
 
 impl<'a, T> std::ops::DerefMut for Tx<'a, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        self.state.mutations += 1;
+        self.scratch.as_deref_mut().expect(SUSPENDED)
     }
 }
 
 impl<'a, T> AsRef<T> for Tx<'a, T> {
     fn as_ref(&self) -> &T {
-        &*self
+        self
     }
 }
 
 impl<'a, T> std::borrow::Borrow<T> for Tx<'a, T> {
     fn borrow(&self) -> &T {
-        &*self
+        self
     }
 }
 
@@ -129,7 +666,7 @@ let mut s = vec![];
     s.push(1);
     assert_eq!(*s, vec![1]);
 }
-assert_eq!(s, vec![]);
+assert!(s.is_empty());
 {
     let mut s = s.tx();
     s.push(1);
@@ -164,71 +701,428 @@ impl<T: Clone> AsTx for T {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+#[cfg(feature = "serde_json")]
+impl<'a> Tx<'a, serde_json::Value> {
+    /// Sets the value at the JSON Pointer `ptr`, creating any missing objects along the way
+    ///
+    /// A `-` as the last token appends to an array
+    pub fn set_pointer(&mut self, ptr: &str, value: serde_json::Value) -> Result<(), PointerError> {
+        use serde_json::Value;
 
-    #[test]
-    fn transaction() {
-        #[derive(Clone, Debug)]
-        struct S {
-            d: Vec<u32>,
-        }
-        impl S {
-            fn tx(&mut self) -> Tx<'_, Self> {
-                Tx::new(self)
-            }
-            fn add(&mut self, item: u32) {
-                self.d.push(item);
+        let mut tokens = pointer_tokens(ptr)?;
+        let last = match tokens.pop() {
+            Some(last) => last,
+            None => {
+                **self = value;
+                return Ok(());
             }
-            fn pop(&mut self) {
-                self.d.pop();
+        };
+
+        let mut target: &mut Value = self;
+        for token in tokens {
+            target = match target {
+                Value::Object(map) => map
+                    .entry(token)
+                    .or_insert_with(|| Value::Object(Default::default())),
+                Value::Array(list) => {
+                    let index = array_index(&token, list.len())?;
+                    &mut list[index]
+                }
+                _ => return Err(PointerError::NotAContainer),
             }
         }
 
-        let mut s = S { d: vec![] };
-        assert_eq!(s.d, vec![]);
-        s.add(1);
-        assert_eq!(s.d, vec![1]);
-        {
-            let mut s = s.tx();
-            s.add(2);
-            assert_eq!(s.d, vec![1, 2]);
+        match target {
+            Value::Object(map) => {
+                map.insert(last, value);
+            }
+            Value::Array(list) if last == "-" => list.push(value),
+            Value::Array(list) => {
+                let index = array_index(&last, list.len())?;
+                list[index] = value
+            }
+            _ => return Err(PointerError::NotAContainer),
         }
-        assert_eq!(s.d, vec![1]);
-        {
-            let mut s = s.tx();
-            s.add(2);
-            assert_eq!(s.d, vec![1, 2]);
-            s.commit();
-            assert_eq!(s.d, vec![1, 2]);
+        Ok(())
+    }
+    /// Removes the value at the JSON Pointer `ptr`, returning it if it existed
+    pub fn remove_pointer(&mut self, ptr: &str) -> Option<serde_json::Value> {
+        use serde_json::Value;
+
+        let mut tokens = pointer_tokens(ptr).ok()?;
+        let last = tokens.pop()?;
+
+        let mut target: &mut Value = self;
+        for token in tokens {
+            target = match target {
+                Value::Object(map) => map.get_mut(&token)?,
+                Value::Array(list) => {
+                    let index = array_index(&token, list.len()).ok()?;
+                    &mut list[index]
+                }
+                _ => return None,
+            }
         }
-        assert_eq!(s.d, vec![1, 2]);
-        {
-            let mut s = s.tx();
-            s.add(3);
-            assert_eq!(s.d, vec![1, 2, 3]);
-            s.rollback();
-            assert_eq!(s.d, vec![1, 2]);
+
+        match target {
+            Value::Object(map) => map.remove(&last),
+            Value::Array(list) => {
+                let index = array_index(&last, list.len()).ok()?;
+                Some(list.remove(index))
+            }
+            _ => None,
         }
-        assert_eq!(s.d, vec![1, 2]);
-        {
-            let mut s = s.tx();
-            s.pop();
-            s.pop();
-            assert_eq!(s.d, vec![]);
+    }
+}
+
+#[cfg(feature = "serde_json")]
+fn pointer_tokens(ptr: &str) -> Result<Vec<String>, PointerError> {
+    match ptr {
+        "" => Ok(Vec::new()),
+        ptr if ptr.starts_with('/') => Ok(ptr[1..].split('/').map(unescape_token).collect()),
+        _ => Err(PointerError::Syntax),
+    }
+}
+
+#[cfg(feature = "serde_json")]
+fn unescape_token(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+#[cfg(feature = "serde_json")]
+fn array_index(token: &str, len: usize) -> Result<usize, PointerError> {
+    // `usize::from_str` also takes a leading `+` or leading zeros, which JSON Pointer doesn't allow
+    if token.starts_with('+') || (token.len() > 1 && token.starts_with('0')) {
+        return Err(PointerError::Index);
+    }
+    token
+        .parse()
+        .ok()
+        .filter(|&index| index < len)
+        .ok_or(PointerError::Index)
+}
+
+/// The error returned by [`Tx::set_pointer`](./struct.Tx.html#method.set_pointer)
+///
+/// New variants may be added, so matches should include a wildcard arm
+#[cfg(feature = "serde_json")]
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PointerError {
+    /// The pointer isn't empty and doesn't start with a `/`
+    Syntax,
+    /// An array index isn't a plain decimal number, or is out of bounds
+    Index,
+    /// The pointer goes through a value which is neither an object nor an array
+    NotAContainer,
+}
+
+#[cfg(feature = "serde_json")]
+impl std::fmt::Display for PointerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            PointerError::Syntax => "pointer must be empty or start with a '/'",
+            PointerError::Index => "invalid array index",
+            PointerError::NotAContainer => {
+                "pointer goes through a value that isn't an object or array"
+            }
+        };
+        f.write_str(msg)
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl std::error::Error for PointerError {}
+
+/// Merges two values which diverged from `base`, using `merge(base, ours, theirs)`
+pub fn three_way<T>(base: &T, ours: &T, theirs: &T, merge: impl FnOnce(&T, &T, &T) -> T) -> T {
+    merge(base, ours, theirs)
+}
+
+/// A transaction detached from its borrow, created with [`Tx::suspend`](./struct.Tx.html#method.suspend)
+pub struct SuspendedTx<T> {
+    state: State<T>,
+}
+
+impl<T: Clone> SuspendedTx<T> {
+    /// Re-attaches the transaction to a borrow, continuing with the value it currently holds
+    ///
+    /// The value may have been changed while it was suspended, so this counts as a mutable access
+    /// for `Tx::is_noop` and `Tx::changed_since`
+    pub fn resume(self, d: &mut T) -> Tx<'_, T> {
+        let mut state = self.state;
+        state.mutations += 1;
+        Tx {
+            scratch: Some(d),
+            state,
+            clone: Box::new(T::clone),
+            panic_slot: None,
+            rollback_hook: None,
+            commit_hooks: Vec::new(),
         }
-        assert_eq!(s.d, vec![1, 2]);
-        {
-            let mut s = s.tx();
-            s.pop();
-            s.commit();
-            assert_eq!(s.d, vec![1]);
-            s.rollback(); // acts like a new transction
-            s.pop();
-            assert_eq!(s.d, vec![]);
+    }
+}
+
+/** A read-only 'transaction' pointer, created with [`Tx::begin_readonly`](./struct.Tx.html#method.begin_readonly)
+
+This never clones the value and only implements `Deref`, so code that is generic over
+`Deref<Target = T>` can use either a `TxRead` or a `Tx`
+
+```compile_fail
+use tx::Tx;
+
+let s = vec![1];
+let mut s = Tx::begin_readonly(&s);
+s.push(2);
+```
+*/
+pub struct TxRead<'a, T>(&'a T);
+
+impl<'a, T> std::ops::Deref for TxRead<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl<'a, T> AsRef<T> for TxRead<'a, T> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<'a, T> std::borrow::Borrow<T> for TxRead<'a, T> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+/** A value paired with a generation counter, for use with [`Tx::commit_checked`](./struct.Tx.html#method.commit_checked)
+
+The generation is bumped on every checked commit, so committing a stale copy over a newer value can be detected
+
+```
+use tx::{AsTx, TxVersioned};
+
+let mut s = TxVersioned::new(vec![1]);
+let stale = s.clone();
+{
+    let mut s = s.tx();
+    s.push(2);
+    s.commit_checked().unwrap();
+}
+assert_eq!(s.generation(), 1);
+{
+    let mut s = s.tx();
+    *s = stale;
+    assert!(s.commit_checked().is_err());
+}
+assert_eq!(*s, vec![1, 2]);
+```
+*/
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TxVersioned<T> {
+    value: T,
+    generation: u64,
+}
+
+impl<T> TxVersioned<T> {
+    /// Wraps a value, starting at generation `0`
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            generation: 0,
         }
-        assert_eq!(s.d, vec![1]);
+    }
+    /// The number of checked commits this value has gone through
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+    /// Unwraps the value
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> std::ops::Deref for TxVersioned<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> std::ops::DerefMut for TxVersioned<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl<'a, T> Tx<'a, TxVersioned<T>> {
+    /// Commits the changes, bumping the generation
+    ///
+    /// This fails if the value is no longer at the generation of the last commit (or the initial state),
+    /// e.g. because a stale copy was assigned over it. On a conflict the value is no longer kept on drop,
+    /// so it is restored to the last commit unless committed some other way
+    pub fn commit_checked(&mut self) -> Result<(), VersionConflict> {
+        let (expected, found) = (self.baseline().generation, self.generation);
+        if expected != found {
+            self.state.save = false;
+            return Err(VersionConflict { expected, found });
+        }
+        self.generation += 1;
+        self.commit();
+        Ok(())
+    }
+}
+
+/// The error returned by [`Tx::commit_checked`](./struct.Tx.html#method.commit_checked)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VersionConflict {
+    /// The generation the transaction was based on
+    pub expected: u64,
+    /// The generation the value was at when committing
+    pub found: u64,
+}
+
+impl std::fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "version conflict: expected generation {}, found {}",
+            self.expected, self.found
+        )
+    }
+}
+
+impl std::error::Error for VersionConflict {}
+
+/** A 'transaction' which owns its value, created with [`Tx::fork`](./struct.Tx.html#method.fork)
+
+This allows trying out changes on a branch, without holding a borrow of the original
+
+```
+use tx::AsTx;
+
+let mut s = vec![1];
+let s = s.tx();
+let mut fork = s.fork();
+fork.push(2);
+fork.commit();
+fork.push(3);
+fork.rollback();
+assert_eq!(*fork, vec![1, 2]);
+assert_eq!(*s, vec![1]);
+```
+*/
+#[derive(Clone, Debug)]
+pub struct TxOwned<T> {
+    value: T,
+    initial: T,
+}
+
+impl<T: Clone> TxOwned<T> {
+    /// Wraps a value, which is also the initial state
+    pub fn new(value: T) -> Self {
+        Self {
+            initial: value.clone(),
+            value,
+        }
+    }
+    /// Commits the changes
+    pub fn commit(&mut self) {
+        self.initial.clone_from(&self.value)
+    }
+    /// Roll back to previous commit (or the initial state)
+    pub fn rollback(&mut self) {
+        self.value.clone_from(&self.initial)
+    }
+}
+
+impl<T> TxOwned<T> {
+    /// Unwraps the value at the last commit (or the initial state), discarding any other changes
+    pub fn into_inner(self) -> T {
+        self.initial
+    }
+}
+
+impl<T> std::ops::Deref for TxOwned<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> std::ops::DerefMut for TxOwned<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transaction() {
+        #[derive(Clone, Debug)]
+        struct S {
+            d: Vec<u32>,
+        }
+        impl S {
+            fn tx(&mut self) -> Tx<'_, Self> {
+                Tx::new(self)
+            }
+            fn add(&mut self, item: u32) {
+                self.d.push(item);
+            }
+            fn pop(&mut self) {
+                self.d.pop();
+            }
+        }
+
+        let mut s = S { d: vec![] };
+        assert_eq!(s.d, Vec::<u32>::new());
+        s.add(1);
+        assert_eq!(s.d, vec![1]);
+        {
+            let mut s = s.tx();
+            s.add(2);
+            assert_eq!(s.d, vec![1, 2]);
+        }
+        assert_eq!(s.d, vec![1]);
+        {
+            let mut s = s.tx();
+            s.add(2);
+            assert_eq!(s.d, vec![1, 2]);
+            s.commit();
+            assert_eq!(s.d, vec![1, 2]);
+        }
+        assert_eq!(s.d, vec![1, 2]);
+        {
+            let mut s = s.tx();
+            s.add(3);
+            assert_eq!(s.d, vec![1, 2, 3]);
+            s.rollback();
+            assert_eq!(s.d, vec![1, 2]);
+        }
+        assert_eq!(s.d, vec![1, 2]);
+        {
+            let mut s = s.tx();
+            s.pop();
+            s.pop();
+            assert_eq!(s.d, Vec::<u32>::new());
+        }
+        assert_eq!(s.d, vec![1, 2]);
+        {
+            let mut s = s.tx();
+            s.pop();
+            s.commit();
+            assert_eq!(s.d, vec![1]);
+            s.rollback(); // acts like a new transction
+            s.pop();
+            assert_eq!(s.d, Vec::<u32>::new());
+        }
+        assert_eq!(s.d, vec![1]);
     }
 
     #[test]
@@ -240,7 +1134,7 @@ mod tests {
             s.push(1);
             assert_eq!(*s, vec![1]);
         }
-        assert_eq!(s, vec![]);
+        assert_eq!(s, Vec::<i32>::new());
         {
             let mut s = s.tx();
             s.push(1);
@@ -260,4 +1154,1010 @@ mod tests {
         }
         assert_eq!(s, vec![1, 2]);
     }
+
+    #[test]
+    fn commit_or_rollback() {
+        let mut s = vec![1];
+        {
+            let mut s = s.tx();
+            s.push(2);
+            s.commit_or_rollback(true);
+            assert_eq!(*s, vec![1, 2]);
+        }
+        assert_eq!(s, vec![1, 2]);
+        {
+            let mut s = s.tx();
+            s.push(3);
+            s.commit_or_rollback(false);
+            assert_eq!(*s, vec![1, 2]);
+        }
+        assert_eq!(s, vec![1, 2]);
+    }
+
+    #[test]
+    fn scope_result() {
+        use std::fmt;
+
+        #[derive(Debug)]
+        struct TooBig(u32);
+        impl fmt::Display for TooBig {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{} is too big", self.0)
+            }
+        }
+        impl std::error::Error for TooBig {}
+
+        fn push(
+            s: &mut Vec<u32>,
+            input: &str,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            let n: u32 = input.parse()?;
+            if n > 10 {
+                return Err(TooBig(n).into());
+            }
+            s.push(n);
+            Ok(())
+        }
+
+        let mut s = vec![1];
+        let len = Tx::scope_result(&mut s, |s| {
+            push(s, "2")?;
+            Ok(s.len())
+        })
+        .unwrap();
+        assert_eq!(len, 2);
+        assert_eq!(s, vec![1, 2]);
+
+        let err = Tx::scope_result(&mut s, |s| {
+            push(s, "3")?;
+            push(s, "foo")
+        })
+        .unwrap_err();
+        assert!(err.is::<std::num::ParseIntError>());
+        assert_eq!(s, vec![1, 2]);
+
+        let err = Tx::scope_result(&mut s, |s| {
+            push(s, "3")?;
+            push(s, "42")
+        })
+        .unwrap_err();
+        assert!(err.is::<TooBig>());
+        assert_eq!(s, vec![1, 2]);
+    }
+
+    #[test]
+    fn restore_from() {
+        let mut s = vec![1];
+        {
+            let mut s = s.tx();
+            s.push(2);
+            s.restore_from(vec![3, 4]);
+            assert_eq!(*s, vec![3, 4]);
+            s.push(5);
+            s.rollback();
+            assert_eq!(*s, vec![3, 4]);
+        }
+        assert_eq!(s, vec![3, 4]);
+
+        let mut mirror = vec![];
+        {
+            let mut s = s.tx().require_commit();
+            s.bind_mirror(&mut mirror);
+            s.restore_from(vec![7]);
+        }
+        assert_eq!(s, vec![7]);
+        assert_eq!(mirror, vec![7]);
+    }
+
+    #[test]
+    fn commit_checked() {
+        let mut s = TxVersioned::new(vec![1]);
+        let stale = s.clone();
+        {
+            let mut s = s.tx();
+            s.push(2);
+            s.commit_checked().unwrap();
+            s.push(3);
+            s.commit_checked().unwrap();
+        }
+        assert_eq!(s.generation(), 2);
+        assert_eq!(*s, vec![1, 2, 3]);
+        {
+            let mut s = s.tx();
+            *s = stale.clone();
+            s.push(4);
+            assert_eq!(
+                s.commit_checked(),
+                Err(VersionConflict {
+                    expected: 2,
+                    found: 0
+                })
+            );
+        }
+        assert_eq!(s.generation(), 2);
+        assert_eq!(*s, vec![1, 2, 3]);
+        {
+            let mut s = s.tx();
+            s.push(4);
+            s.commit_checked().unwrap();
+            *s = stale;
+            assert!(s.commit_checked().is_err());
+        }
+        assert_eq!(s.generation(), 3);
+        assert_eq!(*s, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn split_off_tx() {
+        let mut s = vec![1, 2, 3, 4];
+        {
+            let mut s = s.tx();
+            let tail = s.split_off_tx(2);
+            assert_eq!(tail, vec![3, 4]);
+            assert_eq!(*s, vec![1, 2]);
+            s.rollback();
+            assert_eq!(*s, vec![1, 2, 3, 4]);
+            assert_eq!(tail, vec![3, 4]);
+        }
+        assert_eq!(s, vec![1, 2, 3, 4]);
+        {
+            let mut s = s.tx();
+            let tail = s.split_off_tx(3);
+            assert_eq!(tail, vec![4]);
+            s.commit();
+        }
+        assert_eq!(s, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn is_noop() {
+        let mut s = vec![1];
+        {
+            let s = s.tx();
+            assert_eq!(s.len(), 1);
+            assert!(s.is_noop());
+        }
+        {
+            let mut s = s.tx();
+            s.push(2);
+            s.pop();
+            assert_eq!(*s, vec![1]);
+            assert!(!s.is_noop());
+        }
+    }
+
+    #[test]
+    fn with_clone_fn() {
+        // not Clone
+        #[derive(Debug, PartialEq)]
+        struct S {
+            d: Vec<u32>,
+            cache: u32,
+        }
+        fn snapshot(s: &S) -> S {
+            S {
+                d: s.d.clone(),
+                cache: 0,
+            }
+        }
+
+        let mut s = S {
+            d: vec![1],
+            cache: 42,
+        };
+        {
+            let mut s = Tx::with_clone_fn(&mut s, snapshot);
+            s.d.push(2);
+            s.cache = 43;
+            s.rollback();
+            assert_eq!(s.d, vec![1]);
+            assert_eq!(s.cache, 0);
+            s.d.push(3);
+            s.cache = 44;
+            s.commit();
+            s.rollback();
+            assert_eq!(s.d, vec![1, 3]);
+            assert_eq!(s.cache, 0);
+        }
+        assert_eq!(s.d, vec![1, 3]);
+        assert_eq!(s.cache, 0);
+
+        let reset = 7;
+        {
+            let mut s = Tx::with_clone_fn(&mut s, |s| S {
+                d: s.d.clone(),
+                cache: reset,
+            });
+            s.cache = 8;
+            s.rollback();
+            assert_eq!(s.cache, reset);
+        }
+        assert_eq!(s.cache, reset);
+
+        s.cache = 5;
+        {
+            let s = Tx::with_clone_fn(&mut s, snapshot);
+            assert_eq!(s.cache, 5);
+        }
+        assert_eq!(s.cache, 5);
+    }
+
+    #[test]
+    fn commit_debounced() {
+        use std::time::Duration;
+        // long enough that the second commit is always within it, even on a slow machine
+        let long = Duration::from_secs(60 * 60);
+        let short = Duration::from_millis(50);
+
+        let mut s = vec![];
+        {
+            let mut s = s.tx();
+            s.push(1);
+            assert!(s.commit_debounced(long));
+            s.push(2);
+            assert!(!s.commit_debounced(long));
+            s.rollback();
+            assert_eq!(*s, vec![1]);
+
+            s.push(3);
+            std::thread::sleep(short);
+            assert!(s.commit_debounced(short));
+        }
+        assert_eq!(s, vec![1, 3]);
+    }
+
+    #[test]
+    fn commit_and_release() {
+        let mut s = vec![1];
+        let mut tx = s.tx();
+        tx.push(2);
+        tx.commit_and_release();
+        assert_eq!(s, vec![1, 2]);
+
+        let tx = s.tx();
+        tx.commit_and_release();
+        assert_eq!(s, vec![1, 2]);
+
+        let mut tx = s.tx();
+        tx.push(3);
+        let (d, initial, committed) = tx.into_parts();
+        Tx::from_parts(d, initial, committed).commit_and_release();
+        assert_eq!(s, vec![1, 2, 3]);
+
+        let (tx, d) = s.tx().suspend();
+        d.push(4);
+        tx.resume(d).commit_and_release();
+        assert_eq!(s, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn diff_stats() {
+        let mut s = vec![1, 2, 3];
+        let mut s = s.tx();
+        assert_eq!(s.diff_stats(), DiffStats::default());
+
+        s.extend(&[4, 5]);
+        let expected = DiffStats {
+            added: 2,
+            ..DiffStats::default()
+        };
+        assert_eq!(s.diff_stats(), expected);
+        s.rollback();
+
+        s.truncate(1);
+        let expected = DiffStats {
+            removed: 2,
+            ..DiffStats::default()
+        };
+        assert_eq!(s.diff_stats(), expected);
+        s.rollback();
+
+        s[0] = 10;
+        s[2] = 30;
+        let expected = DiffStats {
+            changed: 2,
+            ..DiffStats::default()
+        };
+        assert_eq!(s.diff_stats(), expected);
+    }
+
+    #[test]
+    fn swap_with() {
+        let (mut a, mut b) = (vec![1], vec![2, 3]);
+        {
+            let (mut a, mut b) = (a.tx(), b.tx());
+            a.push(4);
+            a.swap_with(&mut b);
+            assert_eq!(*a, vec![2, 3]);
+            assert_eq!(*b, vec![1, 4]);
+
+            a.commit();
+            b.rollback();
+            assert_eq!(*b, vec![2, 3]);
+        }
+        assert_eq!(a, vec![2, 3]);
+        assert_eq!(b, vec![2, 3]);
+    }
+
+    #[test]
+    fn commit_map() {
+        let mut s = vec![3, 1];
+        {
+            let mut s = s.tx();
+            s.push(2);
+            s.commit_map(|s| s.sort());
+            s.push(0);
+            s.rollback();
+            assert_eq!(*s, vec![1, 2, 3]);
+        }
+        assert_eq!(s, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn probe() {
+        let mut s = vec![1, 2];
+        let s = s.tx();
+        let first = s.probe(|s| s.first().copied().ok_or("empty"));
+        assert_eq!(first, Ok(1));
+        let third = s.probe(|s| s.get(2).copied().ok_or("too short"));
+        assert_eq!(third, Err("too short"));
+        assert_eq!(*s, vec![1, 2]);
+        assert!(s.is_noop());
+    }
+
+    #[test]
+    fn at_origin() {
+        let mut s = vec![1];
+        {
+            let s = s.tx();
+            assert_eq!(s.at_origin(), None);
+        }
+        let mut s = s.tx().track_origin();
+        assert_eq!(s.at_origin(), Some(true));
+        s.push(2);
+        s.commit();
+        s.push(3);
+        s.commit();
+        assert_eq!(s.at_origin(), Some(false));
+        s.truncate(1);
+        assert_eq!(s.at_origin(), Some(true));
+        s.rollback();
+        assert_eq!(s.at_origin(), Some(false));
+    }
+
+    #[test]
+    fn retry() {
+        let mut s = vec![1];
+        let mut attempt = 0;
+        let res = Tx::retry(&mut s, 3, |s| {
+            attempt += 1;
+            s.push(attempt);
+            if attempt < 3 {
+                return Err(attempt);
+            }
+            Ok(s.len())
+        });
+        assert_eq!(res, Ok(2));
+        assert_eq!(s, vec![1, 3]);
+
+        let mut attempt = 0;
+        let res: Result<(), _> = Tx::retry(&mut s, 2, |s| {
+            attempt += 1;
+            s.push(attempt);
+            Err(attempt)
+        });
+        assert_eq!(res, Err(2));
+        assert_eq!(s, vec![1, 3]);
+
+        let mut s = vec![1];
+        let mut attempt = 0;
+        let res: Result<(), _> = Tx::retry(&mut s, 3, |s| {
+            attempt += 1;
+            assert_eq!(**s, vec![1]);
+            s.push(attempt);
+            s.commit();
+            Err(attempt)
+        });
+        assert_eq!(res, Err(3));
+        assert_eq!(s, vec![1]);
+    }
+
+    #[test]
+    fn from_deref() {
+        let lock = std::sync::Mutex::new(vec![1]);
+        {
+            let mut guard = lock.lock().unwrap();
+            let mut s = Tx::from_deref(&mut guard);
+            s.push(2);
+            assert_eq!(*s, vec![1, 2]);
+        }
+        assert_eq!(*lock.lock().unwrap(), vec![1]);
+        {
+            let mut guard = lock.lock().unwrap();
+            let mut s = Tx::from_deref(&mut guard);
+            s.push(2);
+            s.commit();
+        }
+        assert_eq!(*lock.lock().unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn from_deref_pointers() {
+        let mut s = Box::new(vec![1]);
+        {
+            let mut s = Tx::from_deref(&mut s);
+            s.push(2);
+        }
+        assert_eq!(*s, vec![1]);
+
+        let cell = std::cell::RefCell::new(vec![1]);
+        {
+            let mut s = cell.borrow_mut();
+            let mut s = Tx::from_deref(&mut s);
+            s.push(2);
+            s.commit();
+            s.push(3);
+            s.rollback();
+        }
+        assert_eq!(*cell.borrow(), vec![1, 2]);
+    }
+
+    #[test]
+    fn changed_since() {
+        let mut s = vec![1];
+        let mut s = s.tx();
+        let token = s.token();
+        assert_eq!(s.len(), 1);
+        s.commit();
+        assert!(!s.changed_since(token));
+        s.push(2);
+        assert!(s.changed_since(token));
+
+        let token = s.token();
+        assert!(!s.changed_since(token));
+        s.rollback();
+        assert!(s.changed_since(token));
+    }
+
+    #[test]
+    fn apply_patch() {
+        let (mut a, mut b) = (vec![1], vec![5]);
+        let (mut a, mut b) = (a.tx(), b.tx());
+        a.extend(&[2, 3]);
+        let added = a.diff_stats().added;
+        let patch = a[a.len() - added..].to_vec();
+
+        b.apply_patch(&patch, |b, patch| b.extend(patch));
+        assert_eq!(*b, vec![5, 2, 3]);
+        b.rollback();
+        assert_eq!(*b, vec![5]);
+    }
+
+    #[test]
+    fn begin_readonly() {
+        struct S {
+            d: Vec<u32>,
+        }
+        impl Clone for S {
+            fn clone(&self) -> Self {
+                panic!("should not be cloned")
+            }
+        }
+        fn first(s: &impl std::ops::Deref<Target = S>) -> Option<u32> {
+            s.d.first().copied()
+        }
+
+        let s = S { d: vec![1, 2] };
+        let s = Tx::begin_readonly(&s);
+        assert_eq!(first(&s), Some(1));
+        assert_eq!(s.d.len(), 2);
+    }
+
+    #[test]
+    fn map_ref() {
+        #[derive(Clone)]
+        struct S {
+            d: Vec<u32>,
+            name: &'static str,
+        }
+
+        let mut s = S {
+            d: vec![1],
+            name: "s",
+        };
+        {
+            let mut s = s.tx();
+            assert_eq!(*s.map_ref(|s| &s.d), vec![1]);
+            s.map_mut(|s| &mut s.d).push(2);
+            assert_eq!(*s.map_ref(|s| &s.d), vec![1, 2]);
+            assert_eq!(*s.map_ref(|s| &s.name), "s");
+        }
+        assert_eq!(s.d, vec![1]);
+    }
+
+    #[test]
+    fn clear_uncommitted() {
+        let mut s = vec![1];
+        {
+            let mut s = s.tx();
+            s.push(2);
+            s.commit();
+            s.extend(&[3, 4]);
+            s.clear_uncommitted();
+            assert_eq!(*s, vec![1, 2]);
+            s.push(5);
+        }
+        assert_eq!(s, vec![1, 2]);
+    }
+
+    #[test]
+    fn commit_limited() {
+        let mut s = vec![1];
+        {
+            let mut s = s.tx();
+            s.push(2);
+            assert_eq!(s.commit_limited(2), Ok(()));
+            s.push(3);
+            assert_eq!(
+                s.commit_limited(2),
+                Err(LimitExceeded { max_len: 2, len: 3 })
+            );
+            s.rollback();
+            assert_eq!(*s, vec![1, 2]);
+            s.push(3);
+        }
+        assert_eq!(s, vec![1, 2]);
+        {
+            let mut s = s.tx();
+            s.push(3);
+            s.commit_limited(3).unwrap();
+            s.push(4);
+            assert!(s.commit_limited(3).is_err());
+        }
+        assert_eq!(s, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn commit_returning_diff() {
+        let added = |old: &Vec<u32>, new: &Vec<u32>| -> Vec<u32> {
+            new.iter().filter(|x| !old.contains(x)).copied().collect()
+        };
+
+        let mut s = vec![1];
+        let mut s = s.tx();
+        s.extend(&[2, 3]);
+        assert_eq!(s.commit_returning_diff(added), vec![2, 3]);
+        s.push(4);
+        assert_eq!(s.commit_returning_diff(added), vec![4]);
+        assert_eq!(s.commit_returning_diff(added), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn suspend() {
+        fn external(s: &mut Vec<u32>) {
+            s.push(3);
+        }
+
+        let mut s = vec![1];
+        let tx = {
+            let mut tx = s.tx();
+            tx.push(2);
+            let token = tx.token();
+            let (suspended, s) = tx.suspend();
+            assert_eq!(*s, vec![1, 2]);
+            external(s);
+
+            let mut tx = suspended.resume(s);
+            assert!(tx.changed_since(token));
+            assert_eq!(*tx, vec![1, 2, 3]);
+            tx.rollback();
+            assert_eq!(*tx, vec![1]);
+            tx.push(4);
+            tx.suspend().0
+        };
+        assert_eq!(s, vec![1, 4]);
+        {
+            let mut tx = tx.resume(&mut s);
+            tx.push(5);
+        }
+        assert_eq!(s, vec![1]);
+
+        let mut slot = None;
+        let tx = s.tx().capture_on_panic(&mut slot).suspend().0;
+        assert_eq!(slot, None);
+        tx.resume(&mut s).commit();
+    }
+
+    #[test]
+    fn capture_on_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut s = vec![1];
+        let mut slot = None;
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            let mut s = Tx::new(&mut s).capture_on_panic(&mut slot);
+            s.push(2);
+            panic!("oops");
+        }));
+        assert!(res.is_err());
+        assert_eq!(slot, Some(vec![1, 2]));
+        assert_eq!(s, vec![1]);
+
+        let mut slot = None;
+        {
+            let mut s = Tx::new(&mut s).capture_on_panic(&mut slot);
+            s.push(2);
+        }
+        assert_eq!(slot, None);
+    }
+
+    #[test]
+    fn equivalent_to() {
+        let mut s = vec![1];
+        let mut s = s.tx();
+        s.push(2);
+        assert!(s.equivalent_to(&vec![1, 2]));
+        assert!(!s.equivalent_to(&vec![1]));
+    }
+
+    #[test]
+    fn with_rollback_hook() {
+        let mut reverted = vec![];
+        let mut s = vec![1];
+        {
+            let mut s = s.tx().with_rollback_hook(|s| reverted.push(s.clone()));
+            s.push(2);
+            s.rollback();
+            s.push(3);
+            s.commit();
+            s.push(4);
+            s.rollback();
+            s.push(5);
+        }
+        assert_eq!(s, vec![1, 3]);
+        assert_eq!(reverted, vec![vec![1, 2], vec![1, 3, 4], vec![1, 3, 5]]);
+
+        let mut reverted = vec![];
+        {
+            let mut s = s.tx().with_rollback_hook(|s| reverted.push(s.clone()));
+            s.push(6);
+            s.commit();
+        }
+        assert_eq!(s, vec![1, 3, 6]);
+        assert!(reverted.is_empty());
+    }
+
+    #[test]
+    fn restore_and_clone() {
+        let mut s = vec![1];
+        let mut s = s.tx();
+        s.push(2);
+        s.commit();
+        s.clear();
+        s.push(42);
+        assert_eq!(s.restore_and_clone(), vec![1, 2]);
+        assert_eq!(*s, vec![1, 2]);
+    }
+
+    #[test]
+    fn watch_field() {
+        #[derive(Clone)]
+        struct S {
+            d: Vec<u32>,
+            status: &'static str,
+        }
+
+        let changes = std::sync::Mutex::new(vec![]);
+        let mut s = S {
+            d: vec![],
+            status: "new",
+        };
+        {
+            let mut s = s.tx();
+            s.watch_field(
+                |s| &s.status,
+                |old, new| changes.lock().unwrap().push((*old, *new)),
+            );
+            s.d.push(1);
+            s.commit();
+            assert!(changes.lock().unwrap().is_empty());
+
+            s.status = "draft";
+            s.commit();
+            s.d.push(2);
+            s.commit();
+            s.status = "done";
+            s.rollback();
+        }
+        assert_eq!(*changes.lock().unwrap(), vec![("new", "draft")]);
+    }
+
+    #[test]
+    fn into_parts() {
+        let mut s = vec![1];
+        {
+            let mut tx = s.tx();
+            tx.push(2);
+            let (d, initial, committed) = tx.into_parts();
+            assert_eq!(*d, vec![1, 2]);
+            assert_eq!(initial, vec![1]);
+            assert!(!committed);
+
+            let mut tx = Tx::from_parts(d, initial, committed);
+            tx.push(3);
+            assert_eq!(*tx, vec![1, 2, 3]);
+        }
+        assert_eq!(s, vec![1]);
+        {
+            let mut tx = s.tx();
+            tx.push(2);
+            tx.commit();
+            let (d, initial, committed) = tx.into_parts();
+            assert_eq!(initial, vec![1, 2]);
+            assert!(committed);
+            d.push(3);
+        }
+        assert_eq!(s, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn bind_mirror() {
+        let mut s = vec![1];
+        let mut mirror = vec![];
+        {
+            let mut s = s.tx();
+            s.bind_mirror(&mut mirror);
+            s.push(2);
+            s.commit();
+            s.push(3);
+            s.rollback();
+            s.push(4);
+        }
+        assert_eq!(s, vec![1, 2]);
+        assert_eq!(mirror, vec![1, 2]);
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let mut s = vec![1];
+        let mut mirror = vec![];
+        let mut slot = None;
+        {
+            let mut s = Tx::with_clone_fn(&mut s, Vec::clone)
+                .capture_on_panic(&mut slot)
+                .with_rollback_hook(|_| {});
+            s.bind_mirror(&mut mirror);
+            s.watch_field(|s| &s[0], |_, _| {});
+            assert_send_sync(&s);
+            assert_send_sync(&s.suspend().0);
+        }
+        assert_send_sync(&Tx::begin_readonly(&s));
+    }
+
+    #[test]
+    fn fork() {
+        let mut s = vec![1];
+        {
+            let mut s = s.tx();
+            s.push(2);
+            let mut fork = s.fork();
+            fork.push(3);
+            fork.commit();
+            fork.push(4);
+            assert_eq!(*fork, vec![1, 2, 3, 4]);
+            assert_eq!(*s, vec![1, 2]);
+            fork.rollback();
+            assert_eq!(fork.into_inner(), vec![1, 2, 3]);
+
+            s.commit();
+            assert_eq!(*s, vec![1, 2]);
+        }
+        assert_eq!(s, vec![1, 2]);
+    }
+
+    #[test]
+    fn commit_nonempty() {
+        let mut s = vec![1];
+        {
+            let mut s = s.tx();
+            assert_eq!(s.commit_nonempty(), Err(NoChangesError));
+            s.push(2);
+            s.pop();
+            assert_eq!(s.commit_nonempty(), Err(NoChangesError));
+            s.push(2);
+            assert_eq!(s.commit_nonempty(), Ok(()));
+            assert_eq!(s.commit_nonempty(), Err(NoChangesError));
+        }
+        assert_eq!(s, vec![1, 2]);
+    }
+
+    #[test]
+    fn require_commit() {
+        let mut s = vec![1];
+        {
+            let mut s = s.tx().require_commit();
+            s.push(2);
+            s.commit();
+        }
+        assert_eq!(s, vec![1, 2]);
+
+        s.tx().require_commit().commit_and_release();
+        s.tx().require_commit().settle();
+        {
+            let mut s = s.tx().require_commit();
+            s.push(3);
+            s.commit();
+            s.push(4);
+            s.rollback();
+        }
+        assert_eq!(s, vec![1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "transaction was dropped without being committed")]
+    fn require_commit_uncommitted() {
+        let mut s = vec![1];
+        let mut s = s.tx().require_commit();
+        s.push(2);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "transaction was dropped without being committed")]
+    fn require_commit_after_rollback() {
+        let mut s = vec![1];
+        let mut s = s.tx().require_commit();
+        s.push(2);
+        s.commit();
+        s.rollback();
+        s.push(3);
+    }
+
+    #[test]
+    fn merge_three_way() {
+        let union = |base: &Vec<u32>, ours: &Vec<u32>, theirs: &Vec<u32>| {
+            let mut merged = base.clone();
+            for item in ours.iter().chain(theirs) {
+                if !merged.contains(item) {
+                    merged.push(*item)
+                }
+            }
+            merged
+        };
+
+        let mut a = vec![1, 2];
+        let mut b = a.clone();
+        let mut a = a.tx();
+        let mut b = b.tx();
+        a.push(3);
+        b.push(4);
+        assert_eq!(three_way(&vec![1, 2], &a, &b, union), vec![1, 2, 3, 4]);
+
+        a.merge_three_way(&b, union);
+        assert_eq!(*a, vec![1, 2, 3, 4]);
+        a.rollback();
+        assert_eq!(*a, vec![1, 2]);
+    }
+
+    #[test]
+    fn baseline_mut() {
+        let mut s = vec![1];
+        {
+            let mut s = s.tx().track_origin();
+            s.push(2);
+            s.baseline_mut().push(10);
+            assert_eq!(*s, vec![1, 2]);
+            s.rollback();
+            assert_eq!(*s, vec![1, 10]);
+            assert_eq!(s.at_origin(), Some(false));
+            s.pop();
+            assert_eq!(s.at_origin(), Some(true));
+        }
+        assert_eq!(s, vec![1, 10]);
+    }
+
+    #[test]
+    fn commits_since_rollback() {
+        let mut s = vec![1];
+        let mut s = s.tx();
+        assert_eq!(s.commits_since_rollback(), 0);
+        for i in 2..5 {
+            s.push(i);
+            s.commit();
+        }
+        assert_eq!(s.commits_since_rollback(), 3);
+        s.rollback();
+        assert_eq!(s.commits_since_rollback(), 0);
+        s.commit();
+        assert_eq!(s.commits_since_rollback(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn json_pointer() {
+        use serde_json::json;
+
+        let original = json!({ "name": "s", "tags": ["a", "b"] });
+        let mut s = original.clone();
+        {
+            let mut s = s.tx();
+            s.set_pointer("/name", json!("t")).unwrap();
+            s.set_pointer("/tags/0", json!("c")).unwrap();
+            s.set_pointer("/tags/-", json!("d")).unwrap();
+            s.set_pointer("/meta/a~1b/c", json!(1)).unwrap();
+            assert_eq!(s.remove_pointer("/tags/1"), Some(json!("b")));
+            assert_eq!(s.remove_pointer("/missing"), None);
+            assert_eq!(
+                *s,
+                json!({ "name": "t", "tags": ["c", "d"], "meta": { "a/b": { "c": 1 } } })
+            );
+
+            assert_eq!(s.set_pointer("name", json!(1)), Err(PointerError::Syntax));
+            assert_eq!(s.set_pointer("/tags/5", json!(1)), Err(PointerError::Index));
+            assert_eq!(s.set_pointer("/tags/x", json!(1)), Err(PointerError::Index));
+            assert_eq!(
+                s.set_pointer("/tags/+0", json!(1)),
+                Err(PointerError::Index)
+            );
+            assert_eq!(
+                s.set_pointer("/tags/01", json!(1)),
+                Err(PointerError::Index)
+            );
+            assert_eq!(s.remove_pointer("/tags/+0"), None);
+            assert_eq!(s.remove_pointer("/tags/00"), None);
+            assert_eq!(s.remove_pointer("tags"), None);
+            assert_eq!(s.remove_pointer("/meta/a~1b/c"), Some(json!(1)));
+            assert_eq!(
+                s.set_pointer("/name/first", json!(1)),
+                Err(PointerError::NotAContainer)
+            );
+        }
+        assert_eq!(s, original);
+    }
+
+    #[test]
+    fn settle() {
+        let mut s = vec![1];
+        let mut s = s.tx();
+        s.push(2);
+        s.pop();
+        s.settle();
+        assert_eq!(s.commits_since_rollback(), 0);
+        s.push(2);
+        s.settle();
+        assert_eq!(s.commits_since_rollback(), 1);
+        s.rollback();
+        assert_eq!(*s, vec![1, 2]);
+    }
+
+    #[test]
+    fn detach_baseline() {
+        let mut s = vec![1; 1024];
+        {
+            let mut s = s.tx();
+            s.push(2);
+            s.commit();
+            s.push(3);
+            s.detach_baseline();
+            assert!(s.state.initial.is_none());
+            assert_eq!(s.diff_stats(), DiffStats::default());
+            assert_eq!(s.commit_nonempty(), Err(NoChangesError));
+
+            s.push(4);
+            s.rollback();
+            assert_eq!(s.len(), 1027);
+            assert_eq!(s[1024..], [2, 3, 4]);
+            assert_eq!(s.restore_and_clone().len(), 1027);
+
+            s.commit();
+            s.push(5);
+            s.rollback();
+            assert_eq!(s.len(), 1027);
+            s.push(5);
+        }
+        assert_eq!(s.len(), 1027);
+    }
 }